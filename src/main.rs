@@ -4,8 +4,9 @@ use crossbeam_deque::{Injector, Steal};
 use dashmap::DashMap;
 use regex::Regex;
 use rustc_hash::FxHasher;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::hash::BuildHasherDefault;
 use std::path::{Path, PathBuf};
@@ -31,7 +32,7 @@ fn get_dir_id(path: &Path) -> u32 {
     }
 }
 
-type ResolveCacheKey = (String, u32); // (include, dir_id)
+type ResolveCacheKey = (String, u32, bool); // (include, dir_id, is_quote)
 
 #[derive(Parser, Debug)]
 #[command(
@@ -49,6 +50,44 @@ struct Cli {
     )]
     build_paths: Vec<PathBuf>,
 
+    #[arg(
+        long = "max-cache-age",
+        default_value_t = DEFAULT_MAX_CACHE_AGE_DAYS,
+        help = "Evict cache entries untouched for this many days"
+    )]
+    max_cache_age: u64,
+
+    #[arg(
+        long = "from",
+        value_enum,
+        default_value = "parse",
+        help = "First pipeline phase to run"
+    )]
+    from: Phase,
+
+    #[arg(
+        long = "to",
+        value_enum,
+        default_value = "emit",
+        help = "Last pipeline phase to run"
+    )]
+    to: Phase,
+
+    #[arg(
+        long = "merge-policy",
+        value_enum,
+        default_value = "first-wins",
+        help = "Conflict resolution for duplicate entries when merging"
+    )]
+    merge_policy: MergePolicy,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        help = "Write the merged database to this path instead of stdout (merge only)"
+    )]
+    output: Option<PathBuf>,
+
     #[arg(
         value_enum,
         default_value = "list",
@@ -61,6 +100,34 @@ struct Cli {
 enum Command {
     List,
     Version,
+    Gc,
+    Merge,
+}
+
+/// How `merge` resolves two entries sharing the same `(directory, file)` key.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MergePolicy {
+    /// Keep the first entry seen.
+    FirstWins,
+    /// Keep the last entry seen.
+    LastWins,
+    /// Prefer whichever entry carries `arguments` over a bare `command`.
+    PreferArguments,
+}
+
+/// The stages of the header-discovery pipeline, in execution order. `--from`
+/// and `--to` select a contiguous subrange, so deriving `Ord` from the
+/// declaration order lets us compare phases directly.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    /// Load and deserialize the compilation databases.
+    Parse,
+    /// Extract the raw `#include` list from each source file.
+    Scan,
+    /// Map includes to absolute header paths and synthesize entries.
+    Resolve,
+    /// Serialize the combined database.
+    Emit,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,30 +144,269 @@ struct CompileCommand {
 
 
 
+// ---------------------------------------------------------------------------
+// Persistent include-resolution cache
+// ---------------------------------------------------------------------------
+
+/// Magic prefix identifying a compdb resolver cache sidecar. Stored verbatim
+/// at the head of every cache file and checked on load; a mismatch means the
+/// file was not written by us (or is corrupt) and the cache is discarded.
+const CACHE_MAGIC: &[u8] = b"COMPDBCACHE\0";
+
+/// Schema version of the on-disk cache. Bump this whenever [`CacheEntry`]
+/// changes shape; on load a differing version discards the whole cache rather
+/// than attempting a migration.
+const CACHE_VERSION: u32 = 2;
+
+/// Name of the sidecar written next to each `compile_commands.json`.
+const CACHE_SUFFIX: &str = "compile_commands.json.compdb-cache";
+
+/// Default retention window, in days, for untouched cache entries.
+const DEFAULT_MAX_CACHE_AGE_DAYS: u64 = 90;
+
+/// Seconds since the Unix epoch, saturating to 0 before 1970 (never happens in
+/// practice but keeps the helper total).
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Cheap change-detection fingerprint: modification time plus size. Matching
+/// fingerprints mean we trust the previously extracted and resolved includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    size: u64,
+}
+
+impl Fingerprint {
+    /// Stats `path`, returning `None` when it cannot be read (e.g. deleted).
+    fn of(path: &Path) -> Option<Fingerprint> {
+        let meta = fs::metadata(path).ok()?;
+        let mtime = meta
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some(Fingerprint {
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            size: meta.len(),
+        })
+    }
+}
+
+/// One cached scan result, keyed by a canonicalized path. `resolved` records
+/// the absolute header paths this file resolved through — the dependency edges
+/// used to propagate invalidation when an upstream header changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    fingerprint: Fingerprint,
+    includes: Vec<String>,
+    resolved: Vec<String>,
+    /// Unix seconds of the last scan that wrote or reused this entry. Drives
+    /// age-based garbage collection.
+    #[serde(default)]
+    last_access: u64,
+}
+
+/// In-memory resolver cache, persisted to a versioned sidecar. Shared across
+/// the scan threads, so entries live in a concurrent map.
+#[derive(Debug, Default)]
+struct ResolverCache {
+    entries: FxDashMap<String, CacheEntry>,
+}
+
+impl ResolverCache {
+    /// Loads a cache from `path`. A missing, truncated, wrong-magic or
+    /// wrong-version file yields an empty cache rather than an error — the
+    /// cache is an optimization and must never be the reason a run fails.
+    fn load(path: &Path) -> ResolverCache {
+        let cache = ResolverCache::default();
+        let Ok(bytes) = fs::read(path) else {
+            return cache;
+        };
+        let header_len = CACHE_MAGIC.len() + 4;
+        if bytes.len() < header_len || &bytes[..CACHE_MAGIC.len()] != CACHE_MAGIC {
+            return cache;
+        }
+        let version = u32::from_le_bytes(
+            bytes[CACHE_MAGIC.len()..header_len]
+                .try_into()
+                .expect("slice is exactly 4 bytes"),
+        );
+        if version != CACHE_VERSION {
+            return cache;
+        }
+        if let Ok(map) =
+            serde_json::from_slice::<std::collections::HashMap<String, CacheEntry>>(&bytes[header_len..])
+        {
+            for (key, entry) in map {
+                cache.entries.insert(key, entry);
+            }
+        }
+        cache
+    }
+
+    /// Merges every entry from another cache into this one, last-write-wins.
+    fn merge(&self, other: ResolverCache) {
+        for (key, entry) in other.entries {
+            self.entries.insert(key, entry);
+        }
+    }
+
+    /// Writes the cache back out with the magic prefix and version word ahead
+    /// of the serialized body.
+    fn save(&self, path: &Path) -> Result<()> {
+        let map: std::collections::HashMap<String, CacheEntry> = self
+            .entries
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        let body = serde_json::to_vec(&map)?;
+        let mut bytes = Vec::with_capacity(CACHE_MAGIC.len() + 4 + body.len());
+        bytes.extend_from_slice(CACHE_MAGIC);
+        bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        fs::write(path, bytes).with_context(|| format!("Failed to write cache {}", path.display()))
+    }
+
+    /// Drops entries that can no longer be trusted: a file that has been
+    /// deleted or whose fingerprint changed. Invalidation then propagates
+    /// through the recorded include edges to a fixpoint, so that any entry
+    /// that resolved *through* a changed header is recomputed as well.
+    fn invalidate_stale(&self) {
+        let mut invalid: FxHashSet<String> = FxHashSet::default();
+        for e in self.entries.iter() {
+            let fresh = Fingerprint::of(Path::new(e.key()))
+                .is_some_and(|fp| fp == e.value().fingerprint);
+            if !fresh {
+                invalid.insert(e.key().clone());
+            }
+        }
+
+        loop {
+            let mut grew = false;
+            for e in self.entries.iter() {
+                if invalid.contains(e.key()) {
+                    continue;
+                }
+                if e.value().resolved.iter().any(|r| invalid.contains(r)) {
+                    invalid.insert(e.key().clone());
+                    grew = true;
+                }
+            }
+            if !grew {
+                break;
+            }
+        }
+
+        for key in invalid {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Garbage-collects the cache: drops entries whose underlying file has
+    /// been deleted, and evicts any entry not touched within `max_age_secs`.
+    /// Run on load to keep the cache bounded for long-lived, churning trees.
+    fn gc(&self, now: u64, max_age_secs: u64) {
+        let mut expired = Vec::new();
+        for e in self.entries.iter() {
+            let missing = Fingerprint::of(Path::new(e.key())).is_none();
+            let aged = now.saturating_sub(e.value().last_access) > max_age_secs;
+            if missing || aged {
+                expired.push(e.key().clone());
+            }
+        }
+        for key in expired {
+            self.entries.remove(&key);
+        }
+    }
+}
+
 static INCLUDE_PATTERN: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r#"\s*#\s*include\s+[<"]([^>"]+)[>"]"#).unwrap());
+    LazyLock::new(|| Regex::new(r#"\s*#\s*include\s+([<"])([^>"]+)[>"]"#).unwrap());
+
+/// Whether an include was written with quotes (`"foo.h"`) or angle brackets
+/// (`<foo.h>`). The two follow different search orders in GCC/Clang.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IncludeKind {
+    Quote,
+    Angle,
+}
 
-fn extract_includes(content: &str) -> Vec<String> {
+/// The `#include` directives found in a file, each tagged with its delimiter.
+fn extract_includes(content: &str) -> Vec<(IncludeKind, String)> {
     INCLUDE_PATTERN
         .captures_iter(content)
-        .map(|cap| cap[1].to_string())
+        .map(|cap| {
+            let kind = if &cap[1] == "\"" {
+                IncludeKind::Quote
+            } else {
+                IncludeKind::Angle
+            };
+            (kind, cap[2].to_string())
+        })
         .collect()
 }
 
+/// Header search roots for one compile command, kept in the distinct buckets
+/// the compiler searches in order rather than a single flat list.
+#[derive(Debug, Clone, Default)]
+struct IncludeDirs {
+    /// `-iquote` dirs, consulted only for quoted includes.
+    quote: Vec<PathBuf>,
+    /// `-I` dirs, except those that look like system paths.
+    bracket: Vec<PathBuf>,
+    /// `-isystem`/`-isysroot` dirs, plus `-I` dirs that look like system paths.
+    system: Vec<PathBuf>,
+    /// `-idirafter` dirs, searched after everything else.
+    idirafter: Vec<PathBuf>,
+}
+
+impl IncludeDirs {
+    /// Every directory the compiler may treat as a system root — used to keep
+    /// system headers out of the synthesized entry set.
+    fn system_roots(&self) -> impl Iterator<Item = &PathBuf> {
+        self.system.iter().chain(self.idirafter.iter())
+    }
+}
+
+/// The raw includes discovered in one source file — the serializable product
+/// of the scan phase, dumped when a run stops after `--to scan`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScannedFile {
+    directory: String,
+    file: String,
+    includes: Vec<String>,
+}
+
+/// Typed intermediate carried out of the scan phase: the original commands the
+/// resolve phase still needs, plus the per-file raw include lists.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ScanOutput {
+    commands: Vec<CompileCommand>,
+    scanned: Vec<ScannedFile>,
+}
+
 fn find_header_files(
     compile_commands: &[CompileCommand],
+    cache: &ResolverCache,
+    now: u64,
 ) -> Result<Vec<CompileCommand>> {
     // Build per-file include maps
-    let file_to_includes: FxDashMap<PathBuf, Vec<PathBuf>> = FxDashMap::default();
+    let file_to_includes: FxDashMap<PathBuf, IncludeDirs> = FxDashMap::default();
     let file_to_command: FxDashMap<PathBuf, CompileCommand> = FxDashMap::default();
     let mut all_system_dirs_set: FxHashSet<PathBuf> = FxHashSet::default();
 
     for cmd in compile_commands {
         let file_path = PathBuf::from(&cmd.file);
-        let (project_dirs, system_dirs) = extract_include_directories_for_command(cmd);
-        all_system_dirs_set.extend(system_dirs.clone());
-        let all_dirs: Vec<PathBuf> = project_dirs.into_iter().chain(system_dirs.into_iter()).collect();
-        file_to_includes.insert(file_path.clone(), all_dirs);
+        let dirs = extract_include_directories_for_command(cmd);
+        all_system_dirs_set.extend(dirs.system_roots().cloned());
+        file_to_includes.insert(file_path.clone(), dirs);
         file_to_command.insert(file_path, cmd.clone());
     }
     let all_system_dirs: Vec<PathBuf> = all_system_dirs_set.into_iter().collect();
@@ -144,20 +450,57 @@ fn find_header_files(
                         };
                         if !is_source { continue; }
 
-                        // Get the correct include paths using the context
-                        if let Some(include_dirs) = file_to_includes.get(&context_path) {
-                            if let Ok(content) = fs::read_to_string(&file_path) {
-                                let includes = extract_includes(&content);
-                                for include in includes {
-                                    if let Some(header_path_str) = resolve_header_path(&include, &include_dirs, &file_path, &resolve_cache, &exists_cache) {
-                                        if processed_headers.insert(header_path_str.clone(), context_path.clone()).is_none() {
-                                            if !is_system_header(&header_path_str, &all_system_dirs) {
-                                                let header_path = PathBuf::from(header_path_str);
-                                                local_work.push((header_path, context_path.clone()));
-                                            }
-                                        }
+                        // Canonicalize for a stable cache key that lines up
+                        // with the resolved absolute paths stored as edges.
+                        let canon = fs::canonicalize(&file_path).unwrap_or_else(|_| file_path.clone());
+                        let key = canon.to_string_lossy().into_owned();
+
+                        // Reuse a cached result whenever the file's fingerprint
+                        // is unchanged; `invalidate_stale` has already dropped
+                        // any entry reached through a changed header.
+                        let fresh = cache.entries.get_mut(&key).and_then(|mut entry| {
+                            match Fingerprint::of(&canon) {
+                                Some(fp) if fp == entry.fingerprint => {
+                                    entry.last_access = now;
+                                    Some(entry.resolved.clone())
+                                }
+                                _ => None,
+                            }
+                        });
+
+                        let resolved = if let Some(resolved) = fresh {
+                            resolved
+                        } else if let Some(include_dirs) = file_to_includes.get(&context_path) {
+                            match fs::read_to_string(&file_path) {
+                                Ok(content) => {
+                                    let includes = extract_includes(&content);
+                                    let resolved: Vec<String> = includes
+                                        .iter()
+                                        .filter_map(|(kind, include)| {
+                                            resolve_header_path(include, *kind, &include_dirs, &file_path, &resolve_cache, &exists_cache)
+                                        })
+                                        .collect();
+                                    if let Some(fp) = Fingerprint::of(&canon) {
+                                        let names = includes.into_iter().map(|(_, name)| name).collect();
+                                        cache.entries.insert(
+                                            key,
+                                            CacheEntry { fingerprint: fp, includes: names, resolved: resolved.clone(), last_access: now },
+                                        );
                                     }
+                                    resolved
                                 }
+                                Err(_) => Vec::new(),
+                            }
+                        } else {
+                            Vec::new()
+                        };
+
+                        for header_path_str in resolved {
+                            if processed_headers.insert(header_path_str.clone(), context_path.clone()).is_none()
+                                && !is_system_header(&header_path_str, &all_system_dirs)
+                            {
+                                let header_path = PathBuf::from(header_path_str);
+                                local_work.push((header_path, context_path.clone()));
                             }
                         }
                     }
@@ -191,48 +534,134 @@ fn is_system_header(header_path: &str, system_dirs: &[PathBuf]) -> bool {
     system_dirs.iter().any(|sys_dir| path.starts_with(sys_dir))
 }
 
-fn extract_include_directories_for_command(cmd: &CompileCommand) -> (Vec<PathBuf>, Vec<PathBuf>) {
-    let mut project_dirs = FxHashSet::default();
-    let mut system_dirs = FxHashSet::default();
-
-    let args = if let Some(ref args) = cmd.arguments {
+fn extract_include_directories_for_command(cmd: &CompileCommand) -> IncludeDirs {
+    let raw = if let Some(ref args) = cmd.arguments {
         args.clone()
     } else if let Some(ref command) = cmd.command {
-        command.split_whitespace().map(std::string::ToString::to_string).collect()
+        tokenize(command)
     } else {
-        return (Vec::new(), Vec::new());
+        return IncludeDirs::default();
     };
 
+    // Real build systems frequently pass include dirs only through response
+    // files, so splice those in before classifying any flag.
+    let base = PathBuf::from(&cmd.directory);
+    let args = expand_response_files(&raw, &base);
+
+    let mut dirs = IncludeDirs::default();
+
+    // Pulls the argument of a flag that may be attached (`-Ifoo`) or separate
+    // (`-I foo`); advances the cursor past whatever it consumed.
+    fn take<'a>(args: &'a [String], i: &mut usize, flag: &str) -> Option<&'a str> {
+        let arg = &args[*i];
+        if arg == flag {
+            if *i + 1 < args.len() {
+                *i += 2;
+                return Some(&args[*i - 1]);
+            }
+            *i += 1;
+            return None;
+        }
+        let rest = &arg[flag.len()..];
+        *i += 1;
+        if rest.is_empty() { None } else { Some(rest) }
+    }
+
     let mut i = 0;
     while i < args.len() {
-        if args[i] == "-I" && i + 1 < args.len() {
-            let path = PathBuf::from(&args[i + 1]);
-            if is_system_path(&path) {
-                system_dirs.insert(path);
-            } else {
-                project_dirs.insert(path);
+        let arg = &args[i];
+        if arg == "-iquote" || arg.starts_with("-iquote") && arg.len() > 7 {
+            if let Some(p) = take(&args, &mut i, "-iquote") {
+                dirs.quote.push(PathBuf::from(p));
+            }
+        } else if arg == "-isystem" || arg.starts_with("-isystem") && arg.len() > 8 {
+            if let Some(p) = take(&args, &mut i, "-isystem") {
+                dirs.system.push(PathBuf::from(p));
+            }
+        } else if arg == "-idirafter" || arg.starts_with("-idirafter") && arg.len() > 10 {
+            if let Some(p) = take(&args, &mut i, "-idirafter") {
+                dirs.idirafter.push(PathBuf::from(p));
             }
-            i += 2;
-        } else if args[i].starts_with("-I") {
-            let path_str = &args[i][2..];
-            if !path_str.is_empty() {
-                let path = PathBuf::from(path_str);
+        } else if arg == "-isysroot" || arg.starts_with("-isysroot") && arg.len() > 9 {
+            if let Some(p) = take(&args, &mut i, "-isysroot") {
+                dirs.system.push(PathBuf::from(p));
+            }
+        } else if arg == "-I" || arg.starts_with("-I") {
+            if let Some(p) = take(&args, &mut i, "-I") {
+                let path = PathBuf::from(p);
                 if is_system_path(&path) {
-                    system_dirs.insert(path);
+                    dirs.system.push(path);
                 } else {
-                    project_dirs.insert(path);
+                    dirs.bracket.push(path);
                 }
             }
-            i += 1;
-        } else if args[i] == "-isystem" && i + 1 < args.len() {
-            system_dirs.insert(PathBuf::from(&args[i + 1]));
-            i += 2;
         } else {
             i += 1;
         }
     }
 
-    (project_dirs.into_iter().collect(), system_dirs.into_iter().collect())
+    dirs
+}
+
+/// Splits a command string into tokens, honoring single and double quotes so
+/// paths containing spaces survive intact.
+fn tokenize(command: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut started = false;
+    let mut quote: Option<char> = None;
+
+    for ch in command.chars() {
+        match quote {
+            Some(q) => {
+                if ch == q {
+                    quote = None;
+                } else {
+                    current.push(ch);
+                }
+            }
+            None if ch == '"' || ch == '\'' => {
+                started = true;
+                quote = Some(ch);
+            }
+            None if ch.is_whitespace() => {
+                if started {
+                    tokens.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            None => {
+                started = true;
+                current.push(ch);
+            }
+        }
+    }
+    if started {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Expands `@response-file` arguments in place: each is read relative to the
+/// command's working directory, tokenized, and spliced into the stream.
+/// Unreadable response files are dropped (the real compiler would error, but
+/// we prefer a best-effort scan over aborting).
+fn expand_response_files(args: &[String], base: &Path) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len());
+    for arg in args {
+        if let Some(file) = arg.strip_prefix('@') {
+            let path = {
+                let p = PathBuf::from(file);
+                if p.is_absolute() { p } else { base.join(p) }
+            };
+            if let Ok(content) = fs::read_to_string(&path) {
+                out.extend(tokenize(&content));
+            }
+        } else {
+            out.push(arg.clone());
+        }
+    }
+    out
 }
 
 
@@ -254,7 +683,8 @@ fn batch_check_exists(paths: &[PathBuf], exists_cache: &FxDashMap<PathBuf, bool>
 
 fn resolve_header_path(
     include: &str,
-    include_dirs: &[PathBuf],
+    kind: IncludeKind,
+    include_dirs: &IncludeDirs,
     source_file: &Path,
     cache: &FxDashMap<ResolveCacheKey, Option<String>>,
     exists_cache: &FxDashMap<PathBuf, bool>,
@@ -263,30 +693,33 @@ fn resolve_header_path(
 
     // Get ultra-fast directory ID instead of hashing full PathBuf
     let dir_id = get_dir_id(source_dir);
-    let key = (include.to_string(), dir_id);
+    let is_quote = kind == IncludeKind::Quote;
+    let key = (include.to_string(), dir_id, is_quote);
 
     if let Some(cached) = cache.get(&key) {
         return cached.value().clone();
     }
 
-    // Build candidate paths
-    let relative_path = source_dir.join(include);
-    let mut candidate_paths = vec![relative_path.clone()];
-    candidate_paths.extend(include_dirs.iter().map(|dir| dir.join(include)));
+    // Assemble candidate directories in the compiler's search order. Quoted
+    // includes start at the current file's directory and the `-iquote` dirs;
+    // angled includes skip straight to the `-I` dirs. Both then fall through
+    // `-isystem`/`-isysroot` and finally `-idirafter`.
+    let mut candidate_paths: Vec<PathBuf> = Vec::new();
+    if is_quote {
+        candidate_paths.push(source_dir.join(include));
+        candidate_paths.extend(include_dirs.quote.iter().map(|dir| dir.join(include)));
+    }
+    candidate_paths.extend(include_dirs.bracket.iter().map(|dir| dir.join(include)));
+    candidate_paths.extend(include_dirs.system.iter().map(|dir| dir.join(include)));
+    candidate_paths.extend(include_dirs.idirafter.iter().map(|dir| dir.join(include)));
 
-    // Batch check existence
+    // Batch check existence, then take the first hit in search order.
     let exists_results = batch_check_exists(&candidate_paths, exists_cache);
-
-    let result = if exists_results[0] {
-        fs::canonicalize(&relative_path).ok().and_then(|p| p.to_str().map(String::from))
-    } else {
-        for (i, exists) in exists_results.iter().skip(1).enumerate() {
-            if *exists {
-                return fs::canonicalize(&candidate_paths[i + 1]).ok().and_then(|p| p.to_str().map(String::from));
-            }
-        }
-        None
-    };
+    let result = exists_results
+        .iter()
+        .position(|exists| *exists)
+        .and_then(|idx| fs::canonicalize(&candidate_paths[idx]).ok())
+        .and_then(|p| p.to_str().map(String::from));
 
     cache.insert(key, result.clone());
     result
@@ -298,6 +731,8 @@ fn main() -> Result<()> {
     match cli.command {
         Command::List => list_command(&cli),
         Command::Version => version_command(),
+        Command::Gc => gc_command(&cli),
+        Command::Merge => merge_command(&cli),
     }
 }
 
@@ -306,9 +741,106 @@ fn version_command() -> Result<()> {
     Ok(())
 }
 
+fn gc_command(cli: &Cli) -> Result<()> {
+    let build_paths = if cli.build_paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        cli.build_paths.clone()
+    };
+
+    let now = now_secs();
+    let max_age_secs = cli.max_cache_age.saturating_mul(86_400);
+    let mut swept = 0usize;
+
+    for build_path in build_paths {
+        let cache_path = build_path.join(CACHE_SUFFIX);
+        if !cache_path.exists() {
+            continue;
+        }
+
+        let cache = ResolverCache::load(&cache_path);
+        let before = cache.entries.len();
+        cache.invalidate_stale();
+        cache.gc(now, max_age_secs);
+        let removed = before - cache.entries.len();
+        swept += removed;
+        cache.save(&cache_path)?;
+        eprintln!("{}: evicted {removed} of {before} entries", cache_path.display());
+    }
+
+    eprintln!("Garbage collection removed {swept} cache entries");
+    Ok(())
+}
+
+fn merge_command(cli: &Cli) -> Result<()> {
+    let build_paths = if cli.build_paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        cli.build_paths.clone()
+    };
+
+    // Unlike `list`, `merge` never synthesizes header entries — it only reads
+    // the existing databases and unifies them.
+    let all_commands = parse_phase(&build_paths)?;
+    let before = all_commands.len();
+    let merged = merge_databases(all_commands, cli.merge_policy);
+
+    let output = serde_json::to_string_pretty(&merged)?;
+    match &cli.output {
+        Some(path) => {
+            fs::write(path, output)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        None => println!("{output}"),
+    }
+
+    eprintln!("Merged {before} entries into {}", merged.len());
+    Ok(())
+}
+
+/// Deduplicates entries on the `(directory, file)` key, resolving conflicts
+/// with `policy` while preserving first-seen order.
+fn merge_databases(commands: Vec<CompileCommand>, policy: MergePolicy) -> Vec<CompileCommand> {
+    use std::collections::hash_map::Entry;
+
+    let mut order: Vec<(String, String)> = Vec::new();
+    let mut by_key: HashMap<(String, String), CompileCommand> = HashMap::new();
+
+    for cmd in commands {
+        let key = (cmd.directory.clone(), cmd.file.clone());
+        match by_key.entry(key.clone()) {
+            Entry::Vacant(slot) => {
+                order.push(key);
+                slot.insert(cmd);
+            }
+            Entry::Occupied(mut slot) => {
+                let keep = match policy {
+                    MergePolicy::FirstWins => slot.get().clone(),
+                    MergePolicy::LastWins => cmd,
+                    MergePolicy::PreferArguments => {
+                        // Prefer the entry carrying `arguments`; otherwise keep
+                        // the one already seen (first-wins).
+                        if slot.get().arguments.is_some() || cmd.arguments.is_none() {
+                            slot.get().clone()
+                        } else {
+                            cmd
+                        }
+                    }
+                };
+                slot.insert(keep);
+            }
+        }
+    }
+
+    order.into_iter().filter_map(|key| by_key.remove(&key)).collect()
+}
+
 fn list_command(cli: &Cli) -> Result<()> {
     let start_time = std::time::Instant::now();
-    let mut all_commands = Vec::new();
+
+    if cli.from > cli.to {
+        anyhow::bail!("--from phase ({:?}) comes after --to phase ({:?})", cli.from, cli.to);
+    }
 
     let build_paths = if cli.build_paths.is_empty() {
         vec![PathBuf::from(".")]
@@ -316,33 +848,149 @@ fn list_command(cli: &Cli) -> Result<()> {
         cli.build_paths.clone()
     };
 
+    // PARSE — load the databases, or take the prior stage's output from stdin.
+    let parsed = if cli.from <= Phase::Parse {
+        Some(parse_phase(&build_paths)?)
+    } else {
+        None
+    };
+    if cli.to == Phase::Parse {
+        return emit_json(&parsed.expect("parse output is present when stopping at parse"));
+    }
+
+    // SCAN — extract the raw per-file include lists.
+    let scan = if cli.from <= Phase::Scan {
+        let commands = match parsed {
+            Some(commands) => commands,
+            None => read_stage_input()?,
+        };
+        scan_phase(commands)
+    } else if cli.from == Phase::Resolve {
+        read_stage_input()?
+    } else {
+        ScanOutput::default()
+    };
+    if cli.to == Phase::Scan {
+        return emit_json(&scan);
+    }
+
+    // RESOLVE — map includes to absolute header paths and synthesize entries.
+    let resolved = if cli.from <= Phase::Resolve {
+        let (cache, cache_paths, now) = load_cache(&build_paths, cli);
+        let combined = resolve_phase(&scan.commands, &cache, now)?;
+        for cache_path in &cache_paths {
+            cache.save(cache_path)?;
+        }
+        combined
+    } else {
+        read_stage_input()?
+    };
+    if cli.to == Phase::Resolve {
+        return emit_json(&resolved);
+    }
+
+    // EMIT — serialize the combined database.
+    emit_phase(&resolved, start_time)
+}
+
+/// Loads and deserializes every `compile_commands.json` across the build paths.
+fn parse_phase(build_paths: &[PathBuf]) -> Result<Vec<CompileCommand>> {
+    let mut all_commands = Vec::new();
     for build_path in build_paths {
         let compile_commands_path = build_path.join("compile_commands.json");
-
         if !compile_commands_path.exists() {
             continue;
         }
-
         let content = fs::read_to_string(&compile_commands_path)
             .with_context(|| format!("Failed to read {}", compile_commands_path.display()))?;
-
         let commands: Vec<CompileCommand> = serde_json::from_str(&content)
             .with_context(|| format!("Failed to parse JSON from {}", compile_commands_path.display()))?;
-
         all_commands.extend(commands);
     }
+    Ok(all_commands)
+}
 
-    // Extract header files
-    let header_commands = find_header_files(&all_commands)?;
+/// Extracts the direct `#include` list of each source file, leaving resolution
+/// to the next phase. Carries the original commands forward untouched.
+fn scan_phase(commands: Vec<CompileCommand>) -> ScanOutput {
+    let scanned = commands
+        .iter()
+        .filter_map(|cmd| {
+            let path = PathBuf::from(&cmd.file);
+            let is_source = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .is_some_and(|ext| matches!(ext, "c" | "cpp" | "cc" | "cxx"));
+            if !is_source || !path.exists() {
+                return None;
+            }
+            let content = fs::read_to_string(&path).ok()?;
+            let includes = extract_includes(&content).into_iter().map(|(_, name)| name).collect();
+            Some(ScannedFile {
+                directory: cmd.directory.clone(),
+                file: cmd.file.clone(),
+                includes,
+            })
+        })
+        .collect();
+    ScanOutput { commands, scanned }
+}
 
-    // Combine original commands with header commands
-    all_commands.extend(header_commands);
+/// Runs the header discovery and returns the original commands plus the
+/// synthesized header entries.
+fn resolve_phase(commands: &[CompileCommand], cache: &ResolverCache, now: u64) -> Result<Vec<CompileCommand>> {
+    let header_commands = find_header_files(commands, cache, now)?;
+    let mut combined = commands.to_vec();
+    combined.extend(header_commands);
+    Ok(combined)
+}
 
-    let output = serde_json::to_string_pretty(&all_commands)?;
+/// Serializes the combined database to stdout and logs a timing line.
+fn emit_phase(commands: &[CompileCommand], start_time: std::time::Instant) -> Result<()> {
+    let output = serde_json::to_string_pretty(commands)?;
     println!("{output}");
+    eprintln!(
+        "Generated {} compile commands in {:.3}s",
+        commands.len(),
+        start_time.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Loads, merges and prunes the sidecar caches sitting next to each database,
+/// returning the live cache, the paths to write back, and the run timestamp.
+fn load_cache(build_paths: &[PathBuf], cli: &Cli) -> (ResolverCache, Vec<PathBuf>, u64) {
+    let cache = ResolverCache::default();
+    let mut cache_paths = Vec::new();
+
+    for build_path in build_paths {
+        if !build_path.join("compile_commands.json").exists() {
+            continue;
+        }
+        let cache_path = build_path.join(CACHE_SUFFIX);
+        cache.merge(ResolverCache::load(&cache_path));
+        cache_paths.push(cache_path);
+    }
+
+    // Prune on load: drop entries whose file or transitive headers changed,
+    // then garbage-collect deleted and long-untouched entries.
+    let now = now_secs();
+    cache.invalidate_stale();
+    cache.gc(now, cli.max_cache_age.saturating_mul(86_400));
 
-    let elapsed = start_time.elapsed();
-    eprintln!("Generated {} compile commands in {:.3}s", all_commands.len(), elapsed.as_secs_f64());
+    (cache, cache_paths, now)
+}
+
+/// Reads a prior phase's serialized output from stdin.
+fn read_stage_input<T: DeserializeOwned>() -> Result<T> {
+    let mut buf = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)
+        .context("Failed to read pipeline input from stdin")?;
+    serde_json::from_str(&buf).context("Failed to deserialize pipeline input")
+}
 
+/// Pretty-prints a phase's typed output as JSON.
+fn emit_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
     Ok(())
 }